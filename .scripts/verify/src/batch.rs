@@ -0,0 +1,162 @@
+//! Batch verification of many contracts listed in a `--manifest` file.
+//!
+//! Each entry is verified by invoking this same binary as a subprocess in
+//! its own working directory (current directory is process-global state, so
+//! entries can't share it across threads), asking it for `--format json` and
+//! deserializing its stdout as a `VerificationReport`.
+
+use crate::VerificationReport;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{error, info};
+
+#[derive(thiserror::Error, Debug)]
+pub enum BatchError {
+    #[error("failed to read manifest file {path}: {source}")]
+    Read {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("failed to parse manifest file {path}: {source}")]
+    Parse {
+        path: String,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ManifestEntry {
+    pub repo: String,
+    pub sha: String,
+    pub package: String,
+    pub hash: String,
+    #[serde(default = "default_dir")]
+    pub dir: String,
+}
+
+fn default_dir() -> String {
+    ".".to_string()
+}
+
+#[derive(Serialize)]
+pub struct EntryReport {
+    pub repo: String,
+    pub sha: String,
+    pub package: String,
+    pub report: Option<VerificationReport>,
+    pub passed: bool,
+    pub build_log: String,
+}
+
+pub fn load_manifest(path: &Path) -> Result<Vec<ManifestEntry>, BatchError> {
+    let text = std::fs::read_to_string(path).map_err(|source| BatchError::Read {
+        path: path.display().to_string(),
+        source,
+    })?;
+    serde_json::from_str(&text).map_err(|source| BatchError::Parse {
+        path: path.display().to_string(),
+        source,
+    })
+}
+
+/// Verify every entry in `entries`, reusing `cache_dir` for downloads shared
+/// across entries and running independent entries concurrently. Each entry
+/// gets its own working directory under `work_root`. Returns one report per
+/// entry, in input order.
+pub fn run(entries: &[ManifestEntry], cache_dir: &Path, work_root: &Path) -> Vec<EntryReport> {
+    std::fs::create_dir_all(work_root).ok();
+
+    entries
+        .par_iter()
+        .enumerate()
+        .map(|(index, entry)| verify_entry(index, entry, cache_dir, work_root))
+        .collect()
+}
+
+fn verify_entry(index: usize, entry: &ManifestEntry, cache_dir: &Path, work_root: &Path) -> EntryReport {
+    let entry_dir = work_root.join(format!("entry-{index}"));
+    if let Err(err) = std::fs::create_dir_all(&entry_dir) {
+        return EntryReport {
+            repo: entry.repo.clone(),
+            sha: entry.sha.clone(),
+            package: entry.package.clone(),
+            report: None,
+            passed: false,
+            build_log: format!("failed to create working directory {}: {err}", entry_dir.display()),
+        };
+    }
+
+    info!(
+        "Verifying {} @ {} (package {}) in {}",
+        entry.repo, entry.sha, entry.package, entry_dir.display()
+    );
+
+    let exe = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("verify"));
+    let output = Command::new(exe)
+        .args([
+            "--repo",
+            &entry.repo,
+            "--sha",
+            &entry.sha,
+            "--package",
+            &entry.package,
+            "--hash",
+            &entry.hash,
+            "--dir",
+            &entry.dir,
+            "--cache-dir",
+            &cache_dir.to_string_lossy(),
+            "--format",
+            "json",
+        ])
+        .current_dir(&entry_dir)
+        .output();
+
+    match output {
+        Ok(output) => {
+            let build_log = String::from_utf8_lossy(&output.stderr).to_string();
+            match serde_json::from_slice::<VerificationReport>(&output.stdout) {
+                Ok(report) => {
+                    let passed = report.passed;
+                    if !passed {
+                        error!("Verification failed for {} @ {}", entry.repo, entry.sha);
+                    }
+                    EntryReport {
+                        repo: entry.repo.clone(),
+                        sha: entry.sha.clone(),
+                        package: entry.package.clone(),
+                        passed,
+                        report: Some(report),
+                        build_log,
+                    }
+                }
+                Err(err) => {
+                    error!("Failed to parse verification report for {} @ {}: {}", entry.repo, entry.sha, err);
+                    EntryReport {
+                        repo: entry.repo.clone(),
+                        sha: entry.sha.clone(),
+                        package: entry.package.clone(),
+                        report: None,
+                        passed: false,
+                        build_log: format!(
+                            "failed to parse verification report from stdout ({err}): {}\n{build_log}",
+                            String::from_utf8_lossy(&output.stdout)
+                        ),
+                    }
+                }
+            }
+        }
+        Err(err) => EntryReport {
+            repo: entry.repo.clone(),
+            sha: entry.sha.clone(),
+            package: entry.package.clone(),
+            report: None,
+            passed: false,
+            build_log: format!("failed to spawn verification subprocess: {err}"),
+        },
+    }
+}
@@ -0,0 +1,59 @@
+//! Local content-addressed cache for downloads that repeat across batch
+//! entries: source tarballs, toolchain components, and metadata JSONs. A
+//! cacache store dedupes identical content regardless of the key it's
+//! fetched under, so entries that share a toolchain or a commit only pay
+//! for the download once.
+
+use std::path::{Path, PathBuf};
+
+#[derive(thiserror::Error, Debug)]
+pub enum CacheError {
+    #[error("cache read/write error: {0}")]
+    Cacache(#[from] cacache::Error),
+}
+
+pub struct Cache {
+    root: PathBuf,
+}
+
+impl Cache {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Cache { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Return the cached bytes for `key`, or run `fetch` to obtain them,
+    /// cache the result, and return it.
+    pub fn get_or_fetch<E>(
+        &self,
+        key: &str,
+        fetch: impl FnOnce() -> Result<Vec<u8>, E>,
+    ) -> Result<Vec<u8>, E>
+    where
+        E: From<CacheError>,
+    {
+        if let Some(bytes) = self.try_get(key) {
+            return Ok(bytes);
+        }
+        let bytes = fetch()?;
+        self.put(key, &bytes)?;
+        Ok(bytes)
+    }
+
+    /// Return the cached bytes for `key`, or `None` on a cache miss. Unlike
+    /// [`Cache::get_or_fetch`], never writes to the cache, so callers that
+    /// need to validate freshly-fetched bytes before trusting them (e.g. a
+    /// checksum check) can fetch without poisoning the cache on failure.
+    pub fn try_get(&self, key: &str) -> Option<Vec<u8>> {
+        cacache::read_sync(&self.root, key).ok()
+    }
+
+    /// Write `bytes` into the cache under `key`.
+    pub fn put(&self, key: &str, bytes: &[u8]) -> Result<(), CacheError> {
+        cacache::write_sync(&self.root, key, bytes)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+//! Integrity checks for data fetched from GitHub before it's used to drive a
+//! build: the source archive, its git identity, and the metadata lookup key.
+
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+#[derive(thiserror::Error, Debug)]
+pub enum IntegrityError {
+    #[error("{context} checksum mismatch: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        context: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("unrecognized digest format '{0}' (expected a 64-character hex SHA256 or 'sha256-<base64>')")]
+    InvalidDigestFormat(String),
+    #[error("extracted archive's git tree '{actual}' does not match the commit's tree '{expected}' on GitHub")]
+    GitIdentityMismatch { expected: String, actual: String },
+    #[error("failed to look up commit {sha} for {owner}/{repo} on GitHub: {source}")]
+    CommitLookupFailed {
+        owner: String,
+        repo: String,
+        sha: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("GitHub's response for commit {sha} of {owner}/{repo} had no tree sha")]
+    CommitTreeMissing { owner: String, repo: String, sha: String },
+    #[error("failed to compute the extracted archive's git tree hash: {0}")]
+    GitTreeComputeFailed(String),
+}
+
+/// Normalize a caller-supplied digest into lowercase hex. Accepts either a
+/// plain 64-character hex SHA256 or an SRI-style `sha256-<base64>` digest.
+pub fn normalize_digest(raw: &str) -> Result<String, IntegrityError> {
+    if let Some(b64) = raw.strip_prefix("sha256-") {
+        let decoded = base64::decode(b64)
+            .map_err(|_| IntegrityError::InvalidDigestFormat(raw.to_string()))?;
+        if decoded.len() != 32 {
+            return Err(IntegrityError::InvalidDigestFormat(raw.to_string()));
+        }
+        return Ok(hex::encode(decoded));
+    }
+    if raw.len() == 64 && raw.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(raw.to_ascii_lowercase());
+    }
+    Err(IntegrityError::InvalidDigestFormat(raw.to_string()))
+}
+
+/// Verify the downloaded source archive's bytes against an
+/// `--archive-integrity` digest, if one was provided.
+pub fn verify_archive_bytes(bytes: &[u8], expected_digest: &str) -> Result<(), IntegrityError> {
+    let expected = normalize_digest(expected_digest)?;
+    let actual = hex::encode(Sha256::digest(bytes));
+    if actual != expected {
+        return Err(IntegrityError::ChecksumMismatch {
+            context: "source archive".to_string(),
+            expected,
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// Confirm the extracted archive actually contains the commit we asked for.
+/// The extracted directory name GitHub's archive endpoint uses is embedded
+/// in the archive itself and so is attacker-controlled; instead, fetch the
+/// commit's tree sha from GitHub's REST API and compare it against the git
+/// tree hash of the extracted contents, computed independently with `git`.
+pub fn verify_git_identity(extracted_dir: &Path, owner: &str, repo: &str, sha: &str) -> Result<(), IntegrityError> {
+    let expected = fetch_commit_tree_sha(owner, repo, sha)?;
+    let actual = compute_git_tree_hash(extracted_dir)?;
+    if actual != expected {
+        return Err(IntegrityError::GitIdentityMismatch { expected, actual });
+    }
+    Ok(())
+}
+
+fn fetch_commit_tree_sha(owner: &str, repo: &str, sha: &str) -> Result<String, IntegrityError> {
+    let url = format!("https://api.github.com/repos/{owner}/{repo}/commits/{sha}");
+    let lookup_failed = |source: reqwest::Error| IntegrityError::CommitLookupFailed {
+        owner: owner.to_string(),
+        repo: repo.to_string(),
+        sha: sha.to_string(),
+        source,
+    };
+    let json: serde_json::Value = reqwest::blocking::Client::new()
+        .get(&url)
+        .header("User-Agent", "stellar-contract-verifications")
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .and_then(|r| r.error_for_status())
+        .map_err(lookup_failed)?
+        .json()
+        .map_err(lookup_failed)?;
+    json.get("commit")
+        .and_then(|c| c.get("tree"))
+        .and_then(|t| t.get("sha"))
+        .and_then(|s| s.as_str())
+        .map(str::to_string)
+        .ok_or_else(|| IntegrityError::CommitTreeMissing {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            sha: sha.to_string(),
+        })
+}
+
+/// Compute the git tree hash of `dir`'s contents by shelling out to `git`
+/// against a scratch `GIT_DIR` outside of `dir`, so the real object database
+/// of any `.git` the archive happens to contain isn't disturbed.
+fn compute_git_tree_hash(dir: &Path) -> Result<String, IntegrityError> {
+    let git_dir = std::env::temp_dir().join(format!("verify-git-identity-{}", std::process::id()));
+    let result = (|| -> Result<String, IntegrityError> {
+        std::fs::create_dir_all(&git_dir).map_err(|e| IntegrityError::GitTreeComputeFailed(e.to_string()))?;
+        let run = |args: &[&str]| -> Result<std::process::Output, IntegrityError> {
+            std::process::Command::new("git")
+                .args(args)
+                .env("GIT_DIR", &git_dir)
+                .env("GIT_WORK_TREE", dir)
+                .output()
+                .map_err(|e| IntegrityError::GitTreeComputeFailed(e.to_string()))
+        };
+        for args in [["init", "-q"].as_slice(), &["add", "-A"]] {
+            let output = run(args)?;
+            if !output.status.success() {
+                return Err(IntegrityError::GitTreeComputeFailed(format!(
+                    "git {} failed: {}",
+                    args.join(" "),
+                    String::from_utf8_lossy(&output.stderr)
+                )));
+            }
+        }
+        let write_tree = run(&["write-tree"])?;
+        if !write_tree.status.success() {
+            return Err(IntegrityError::GitTreeComputeFailed(format!(
+                "git write-tree failed: {}",
+                String::from_utf8_lossy(&write_tree.stderr)
+            )));
+        }
+        Ok(String::from_utf8_lossy(&write_tree.stdout).trim().to_string())
+    })();
+    let _ = std::fs::remove_dir_all(&git_dir);
+    result
+}
+
+/// The metadata JSON is fetched from a URL keyed by `--hash`, the same hash
+/// the reference Wasm module is published under, so confirm that key really
+/// is that module's SHA256 before trusting the metadata it's paired with.
+pub fn verify_metadata(hash_param: &str, reference_wasm_bytes: &[u8]) -> Result<(), IntegrityError> {
+    if hash_param.len() != 64 || !hash_param.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(IntegrityError::InvalidDigestFormat(hash_param.to_string()));
+    }
+    let expected = hash_param.to_ascii_lowercase();
+    let actual = hex::encode(Sha256::digest(reference_wasm_bytes));
+    if actual != expected {
+        return Err(IntegrityError::ChecksumMismatch {
+            context: "reference Wasm module".to_string(),
+            expected,
+            actual,
+        });
+    }
+    Ok(())
+}
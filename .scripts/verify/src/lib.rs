@@ -0,0 +1,301 @@
+//! Reproducibility verification for Stellar contract Wasm builds, as a
+//! library.
+//!
+//! [`verify`] takes a [`Request`] and returns a [`VerificationReport`];
+//! `main.rs` is a thin wrapper around it, and the batch runner drives it as
+//! a subprocess per entry since the build steps rely on the process's
+//! current directory.
+
+pub mod batch;
+pub mod cache;
+pub mod integrity;
+pub mod metadata;
+pub mod toolchain;
+pub mod wasmdiff;
+
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use tracing::{error, info};
+
+#[derive(thiserror::Error, Debug)]
+pub enum VerifyError {
+    #[error("invalid repository format '{0}' (expected 'owner/repo')")]
+    InvalidRepo(String),
+    #[error("http error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    Integrity(#[from] integrity::IntegrityError),
+    #[error(transparent)]
+    Metadata(#[from] metadata::MetadataError),
+    #[error(transparent)]
+    Toolchain(#[from] toolchain::ToolchainError),
+    #[error(transparent)]
+    Cache(#[from] cache::CacheError),
+    #[error("invalid Wasm metadata JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Stellar contract build failed with exit code {code:?}\nstdout: {stdout}\nstderr: {stderr}")]
+    BuildFailed {
+        code: Option<i32>,
+        stdout: String,
+        stderr: String,
+    },
+    #[error("Wasm optimization failed with exit code {0:?}")]
+    OptimizeFailed(Option<i32>),
+    #[error("failed to read built Wasm file - unoptimized error: {unoptimized}, optimized error: {optimized}")]
+    WasmReadFailed { unoptimized: String, optimized: String },
+    #[error("expected the extracted source archive to contain exactly one top-level directory, found {0}")]
+    UnexpectedArchiveLayout(usize),
+}
+
+/// Everything needed to verify that a contract's published Wasm hash is
+/// reproducible from its source.
+pub struct Request {
+    /// `owner/repo` on GitHub.
+    pub repo: String,
+    /// The commit to build from.
+    pub sha: String,
+    /// The cargo package to build within the source tree.
+    pub package: String,
+    /// The SHA256 hash the built Wasm is expected to reproduce, and the key
+    /// the stellar-contract-wasms repo's metadata is fetched by.
+    pub hash: String,
+    /// Directory within the source tree to build from.
+    pub dir: String,
+    /// Expected digest of the downloaded source archive (hex SHA256 or
+    /// SRI-style `sha256-<base64>`). Verified before unpacking when set.
+    pub archive_integrity: Option<String>,
+    /// Directory for the content-addressed cache of downloads.
+    pub cache_dir: PathBuf,
+}
+
+/// The outcome of a verification attempt.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct VerificationReport {
+    pub toolchain: String,
+    pub target: String,
+    pub build_command: String,
+    pub expected_hash: String,
+    pub unoptimized_hash: Option<String>,
+    pub optimized_hash: Option<String>,
+    /// Which variant ("unoptimized" or "optimized") matched `expected_hash`,
+    /// if either did.
+    pub matched_variant: Option<String>,
+    pub passed: bool,
+}
+
+/// Rebuild the contract described by `request` and check whether it
+/// reproduces `request.hash`.
+pub fn verify(request: Request) -> Result<VerificationReport, VerifyError> {
+    let cache = cache::Cache::new(&request.cache_dir);
+
+    let metadata_url = format!(
+        "https://github.com/leighmcculloch/stellar-contract-wasms/raw/refs/heads/main/meta/{}.json",
+        request.hash
+    );
+    info!("Fetching Wasm metadata for hash {} from {}", request.hash, metadata_url);
+    let metadata_bytes = cache.get_or_fetch(&metadata_url, || {
+        reqwest::blocking::get(&metadata_url)
+            .and_then(|r| r.bytes())
+            .map(|b| b.to_vec())
+            .map_err(VerifyError::Http)
+    })?;
+
+    let reference_wasm_url = wasmdiff::reference_wasm_url(&request.hash);
+    info!("Fetching reference Wasm module from {} to verify the metadata lookup key", reference_wasm_url);
+    let reference_wasm_bytes = cache.get_or_fetch(&reference_wasm_url, || {
+        reqwest::blocking::get(&reference_wasm_url)
+            .and_then(|r| r.bytes())
+            .map(|b| b.to_vec())
+            .map_err(VerifyError::Http)
+    })?;
+    integrity::verify_metadata(&request.hash, &reference_wasm_bytes)?;
+    info!("Verified hash {} against the reference Wasm module", request.hash);
+
+    let json: serde_json::Value = serde_json::from_slice(&metadata_bytes)?;
+    let build_plan = metadata::parse_build_plan(&json)?;
+    let toolchain = build_plan.toolchain.clone();
+    info!(
+        "Resolved build plan: toolchain={} target={:?} features={:?} manifest_path={:?} profile={:?} rustflags={:?}",
+        build_plan.toolchain,
+        build_plan.target,
+        build_plan.features,
+        build_plan.manifest_path,
+        build_plan.profile,
+        build_plan.rustflags
+    );
+
+    let toolchain_prefix = Path::new("toolchain");
+    let installed = toolchain::install(&toolchain, toolchain_prefix, build_plan.target.as_deref(), Some(&cache))?;
+    let (target, toolchain_bin) = match &installed {
+        toolchain::InstalledToolchain::Verified { prefix, target } => {
+            info!(
+                "Installed checksum-verified toolchain {} (target {}) into {}",
+                toolchain,
+                target,
+                prefix.display()
+            );
+            (target.clone(), Some(prefix.join("bin")))
+        }
+        toolchain::InstalledToolchain::Rustup { target } => {
+            info!("Installed toolchain {} (target {}) via rustup fallback", toolchain, target);
+            (target.clone(), None)
+        }
+    };
+
+    let code_path = Path::new("code");
+    let wasm_path = Path::new("wasm");
+    let build_dir = code_path.join(&request.dir);
+
+    let parts: Vec<&str> = request.repo.split('/').collect();
+    if parts.len() != 2 {
+        return Err(VerifyError::InvalidRepo(request.repo.clone()));
+    }
+    let (owner, repo) = (parts[0], parts[1]);
+
+    let url = format!("https://github.com/{}/{}/archive/{}.tar.gz", owner, repo, request.sha);
+    info!("Downloading archive from {}", url);
+    let bytes = cache.get_or_fetch(&url, || {
+        reqwest::blocking::get(&url)
+            .and_then(|r| r.bytes())
+            .map(|b| b.to_vec())
+            .map_err(VerifyError::Http)
+    })?;
+    info!("Successfully downloaded {} bytes", bytes.len());
+
+    if let Some(expected) = &request.archive_integrity {
+        info!("Verifying source archive integrity against {}", expected);
+        integrity::verify_archive_bytes(&bytes, expected)?;
+        info!("Source archive integrity verified");
+    }
+
+    info!("Creating code directory");
+    std::fs::create_dir_all(code_path)?;
+
+    info!("Extracting source code archive");
+    let tar = flate2::read::GzDecoder::new(Cursor::new(bytes));
+    let mut archive = tar::Archive::new(tar);
+    archive.unpack(code_path)?;
+
+    info!("Moving extracted contents to code directory");
+    let mut extracted_dirs: Vec<_> = std::fs::read_dir(code_path)?.filter_map(|e| e.ok()).collect();
+    if extracted_dirs.len() != 1 {
+        return Err(VerifyError::UnexpectedArchiveLayout(extracted_dirs.len()));
+    }
+    let extracted_dir = extracted_dirs.remove(0).path();
+    if !extracted_dir.is_dir() {
+        return Err(VerifyError::UnexpectedArchiveLayout(1));
+    }
+    integrity::verify_git_identity(&extracted_dir, owner, repo, &request.sha)?;
+    info!("Verified extracted archive's git tree matches commit {}", request.sha);
+    for entry in std::fs::read_dir(&extracted_dir)? {
+        let entry = entry?;
+        let target_path = code_path.join(entry.file_name());
+        std::fs::rename(entry.path(), target_path)?;
+    }
+    std::fs::remove_dir(extracted_dir)?;
+
+    let mut build_command = std::process::Command::new("stellar");
+    build_command
+        .args([
+            "contract", "build", "--package", &request.package, "--out-dir", "../wasm/", "--target", &target,
+        ])
+        .current_dir(&build_dir);
+    if !build_plan.features.is_empty() {
+        build_command.args(["--features", &build_plan.features.join(",")]);
+    }
+    if let Some(manifest_path) = &build_plan.manifest_path {
+        // Recorded relative to the repository root, while the build runs in
+        // `build_dir`, so resolve it against the extracted source tree.
+        build_command
+            .arg("--manifest-path")
+            .arg(std::fs::canonicalize(code_path)?.join(manifest_path));
+    }
+    if let Some(profile) = &build_plan.profile {
+        build_command.args(["--profile", profile]);
+    }
+    if let Some(rustflags) = &build_plan.rustflags {
+        build_command.env("RUSTFLAGS", rustflags);
+    }
+    if let Some(toolchain_bin) = &toolchain_bin {
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        let mut paths = vec![toolchain_bin.clone()];
+        paths.extend(std::env::split_paths(&path));
+        build_command.env("PATH", std::env::join_paths(paths).map_err(std::io::Error::other)?);
+    } else {
+        build_command.env("RUSTUP_TOOLCHAIN", &toolchain);
+    }
+    let build_command_str = format!("{:?}", build_command);
+    info!("Building Stellar contract '{}' in directory {} for target {}: {}", request.package, build_dir.display(), target, build_command_str);
+    let output = build_command.output()?;
+    if !output.status.success() {
+        return Err(VerifyError::BuildFailed {
+            code: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        });
+    }
+
+    let package_name = request.package.replace('-', "_");
+    let wasm_file = wasm_path.join(format!("{}.wasm", package_name));
+    let optimized_file = wasm_path.join(format!("{}.optimized.wasm", package_name));
+
+    info!("Optimizing Wasm file");
+    let optimize_status = std::process::Command::new("stellar")
+        .args(["contract", "optimize", "--wasm", &wasm_file.to_string_lossy()])
+        .status()?;
+    if !optimize_status.success() {
+        return Err(VerifyError::OptimizeFailed(optimize_status.code()));
+    }
+
+    let unoptimized_bytes = std::fs::read(&wasm_file).ok();
+    let optimized_bytes = std::fs::read(&optimized_file).ok();
+    if unoptimized_bytes.is_none() && optimized_bytes.is_none() {
+        return Err(VerifyError::WasmReadFailed {
+            unoptimized: format!("could not read {}", wasm_file.display()),
+            optimized: format!("could not read {}", optimized_file.display()),
+        });
+    }
+
+    let unoptimized_hash = unoptimized_bytes.as_ref().map(|b| hex::encode(Sha256::digest(b)));
+    let optimized_hash = optimized_bytes.as_ref().map(|b| hex::encode(Sha256::digest(b)));
+    info!("Computed hashes: unoptimized={:?} optimized={:?}", unoptimized_hash, optimized_hash);
+
+    let matched_variant = if unoptimized_hash.as_deref() == Some(request.hash.as_str()) {
+        Some("unoptimized".to_string())
+    } else if optimized_hash.as_deref() == Some(request.hash.as_str()) {
+        Some("optimized".to_string())
+    } else {
+        None
+    };
+    let passed = matched_variant.is_some();
+
+    if passed {
+        info!("✓ Hash verification successful using {} Wasm file", matched_variant.as_deref().unwrap());
+    } else {
+        error!(
+            "✗ Hash verification failed - expected: {}, unoptimized: {:?}, optimized: {:?}",
+            request.hash, unoptimized_hash, optimized_hash
+        );
+        let built_bytes = unoptimized_bytes.as_ref().or(optimized_bytes.as_ref());
+        if let Some(built_bytes) = built_bytes {
+            match wasmdiff::diff(built_bytes, &reference_wasm_bytes) {
+                Ok(lines) => info!("Wasm diff report:\n{}", wasmdiff::format_report(&lines)),
+                Err(err) => error!("Failed to diff built Wasm against reference module: {}", err),
+            }
+        }
+    }
+
+    Ok(VerificationReport {
+        toolchain,
+        target,
+        build_command: build_command_str,
+        expected_hash: request.hash,
+        unoptimized_hash,
+        optimized_hash,
+        matched_variant,
+        passed,
+    })
+}
@@ -0,0 +1,167 @@
+//! Parsing of the full `sc_meta_v0` reproducibility envelope (toolchain,
+//! target, features, manifest path, profile, `RUSTFLAGS`) out of a Wasm
+//! module's metadata JSON into a concrete [`BuildPlan`].
+
+use serde_json::Value;
+
+#[derive(thiserror::Error, Debug)]
+pub enum MetadataError {
+    #[error("Wasm metadata is missing required key 'rsver' (Rust toolchain version)")]
+    MissingToolchain,
+    #[error("Wasm metadata key '{key}' has unparseable value '{value}'")]
+    Unparsable { key: String, value: String },
+}
+
+/// The resolved build plan for reproducing a Wasm module, assembled from the
+/// `sc_meta_v0` entries present in its metadata.
+#[derive(Debug, Default)]
+pub struct BuildPlan {
+    /// `rsver` — the Rust toolchain version, e.g. `1.81.0`.
+    pub toolchain: String,
+    /// `rstarget` — the compilation target, when the metadata declares one.
+    /// When absent, the toolchain installer picks the wasm target: from the
+    /// channel manifest, or from the toolchain version under the rustup
+    /// fallback.
+    pub target: Option<String>,
+    /// `rsfeatures` — a comma-separated list of enabled cargo features.
+    pub features: Vec<String>,
+    /// `rsmanifestpath` — path to the package/workspace manifest to build,
+    /// relative to the repository root.
+    pub manifest_path: Option<String>,
+    /// `rsprofile` — the cargo/`stellar contract build` optimization profile
+    /// (e.g. `release`, or a custom named profile).
+    pub profile: Option<String>,
+    /// `rsflags` — `RUSTFLAGS` recorded at build time.
+    pub rustflags: Option<String>,
+}
+
+fn find_meta_entry<'a>(entries: &'a [Value], key: &str) -> Option<&'a Value> {
+    entries.iter().find_map(|item| {
+        let meta = item.get("sc_meta_v0")?;
+        if meta.get("key").and_then(|k| k.as_str()) != Some(key) {
+            return None;
+        }
+        meta.get("val")
+    })
+}
+
+/// Look up `key`'s value among the `sc_meta_v0` entries. `None` means the
+/// key is absent; `Unparsable` means it's present but not a JSON string, so
+/// callers can't silently default a key that was actually malformed.
+fn find_meta_str<'a>(entries: &'a [Value], key: &str) -> Result<Option<&'a str>, MetadataError> {
+    match find_meta_entry(entries, key) {
+        None => Ok(None),
+        Some(val) => val.as_str().map(Some).ok_or_else(|| MetadataError::Unparsable {
+            key: key.to_string(),
+            value: val.to_string(),
+        }),
+    }
+}
+
+/// Parse a [`BuildPlan`] out of the `sc_meta_v0` entries in a Wasm module's
+/// metadata JSON, applying sensible defaults for keys that are absent and
+/// raising [`MetadataError::Unparsable`] for keys that are present but
+/// malformed.
+pub fn parse_build_plan(json: &Value) -> Result<BuildPlan, MetadataError> {
+    let entries = json.as_array().map(Vec::as_slice).unwrap_or(&[]);
+
+    let toolchain = find_meta_str(entries, "rsver")?
+        .map(str::to_string)
+        .ok_or(MetadataError::MissingToolchain)?;
+
+    let target = find_meta_str(entries, "rstarget")?.map(str::to_string);
+
+    let features = match find_meta_str(entries, "rsfeatures")? {
+        Some(val) if !val.trim().is_empty() => {
+            val.split(',').map(|f| f.trim().to_string()).collect()
+        }
+        _ => Vec::new(),
+    };
+
+    let manifest_path = find_meta_str(entries, "rsmanifestpath")?.map(str::to_string);
+
+    let profile = match find_meta_str(entries, "rsprofile")? {
+        Some(val) if !val.trim().is_empty() => Some(val.to_string()),
+        _ => None,
+    };
+
+    let rustflags = find_meta_str(entries, "rsflags")?.and_then(|val| {
+        if val.trim().is_empty() {
+            None
+        } else {
+            Some(val.to_string())
+        }
+    });
+
+    Ok(BuildPlan {
+        toolchain,
+        target,
+        features,
+        manifest_path,
+        profile,
+        rustflags,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn meta_entry(key: &str, val: Value) -> Value {
+        json!({ "sc_meta_v0": { "key": key, "val": val } })
+    }
+
+    #[test]
+    fn missing_rsver_errors() {
+        let json = json!([]);
+        let err = parse_build_plan(&json).unwrap_err();
+        assert!(matches!(err, MetadataError::MissingToolchain));
+    }
+
+    #[test]
+    fn malformed_rsver_errors() {
+        let json = Value::Array(vec![meta_entry("rsver", json!(181))]);
+        let err = parse_build_plan(&json).unwrap_err();
+        assert!(matches!(err, MetadataError::Unparsable { key, .. } if key == "rsver"));
+    }
+
+    #[test]
+    fn malformed_rsfeatures_errors() {
+        let json = Value::Array(vec![
+            meta_entry("rsver", json!("1.81.0")),
+            meta_entry("rsfeatures", json!(["a", "b"])),
+        ]);
+        let err = parse_build_plan(&json).unwrap_err();
+        assert!(matches!(err, MetadataError::Unparsable { key, .. } if key == "rsfeatures"));
+    }
+
+    #[test]
+    fn empty_rsfeatures_is_no_features() {
+        let json = Value::Array(vec![
+            meta_entry("rsver", json!("1.81.0")),
+            meta_entry("rsfeatures", json!("")),
+        ]);
+        let plan = parse_build_plan(&json).unwrap();
+        assert!(plan.features.is_empty());
+    }
+
+    #[test]
+    fn full_plan_parses() {
+        let json = Value::Array(vec![
+            meta_entry("rsver", json!("1.81.0")),
+            meta_entry("rstarget", json!("wasm32-unknown-unknown")),
+            meta_entry("rsfeatures", json!("a, b")),
+            meta_entry("rsmanifestpath", json!("Cargo.toml")),
+            meta_entry("rsprofile", json!("release")),
+            meta_entry("rsflags", json!("-C opt-level=3")),
+        ]);
+        let plan = parse_build_plan(&json).unwrap();
+        assert_eq!(plan.toolchain, "1.81.0");
+        assert_eq!(plan.target.as_deref(), Some("wasm32-unknown-unknown"));
+        assert_eq!(plan.features, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(plan.manifest_path.as_deref(), Some("Cargo.toml"));
+        assert_eq!(plan.profile.as_deref(), Some("release"));
+        assert_eq!(plan.rustflags.as_deref(), Some("-C opt-level=3"));
+    }
+}
@@ -0,0 +1,388 @@
+//! Manifest-based Rust toolchain installation.
+//!
+//! Fetches the official release channel manifest from static.rust-lang.org,
+//! resolves the components we need from it, and verifies each downloaded
+//! artifact's SHA256 against the hash the manifest declares before unpacking
+//! it. Falls back to `rustup install` when the manifest can't be reached.
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::process;
+use tracing::{info, warn};
+
+const HOST_TARGET: &str = "x86_64-unknown-linux-gnu";
+const WASM_TARGET_CANDIDATES: &[&str] = &["wasm32v1-none", "wasm32-unknown-unknown"];
+
+#[derive(thiserror::Error, Debug)]
+pub enum ToolchainError {
+    #[error("failed to fetch channel manifest for toolchain {version}: {source}")]
+    ManifestFetch {
+        version: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("failed to parse channel manifest for toolchain {version}: {source}")]
+    ManifestParse {
+        version: String,
+        #[source]
+        source: toml::de::Error,
+    },
+    #[error("no available wasm target found in channel manifest for toolchain {version} (tried {candidates:?})")]
+    NoWasmTarget {
+        version: String,
+        candidates: Vec<&'static str>,
+    },
+    #[error("component '{component}' is not available for target {target} in toolchain {version}")]
+    ComponentUnavailable {
+        component: String,
+        target: String,
+        version: String,
+    },
+    #[error("failed to download component '{component}': {source}")]
+    Download {
+        component: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("checksum mismatch for component '{component}': expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        component: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("failed to unpack component '{component}': {source}")]
+    Unpack {
+        component: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Cache(#[from] crate::cache::CacheError),
+}
+
+/// A channel manifest as published at
+/// `https://static.rust-lang.org/dist/channel-rust-<version>.toml`.
+#[derive(Deserialize)]
+struct ChannelManifest {
+    #[serde(rename = "pkg")]
+    packages: BTreeMap<String, Package>,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    #[serde(rename = "target")]
+    targets: BTreeMap<String, PackageTarget>,
+}
+
+#[derive(Deserialize)]
+struct PackageTarget {
+    available: bool,
+    #[serde(default)]
+    xz_url: Option<String>,
+    #[serde(default)]
+    xz_hash: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    hash: Option<String>,
+}
+
+/// The resolved outcome of installing a toolchain: either we verified and
+/// unpacked it ourselves into `prefix`, or we fell back to `rustup install`
+/// and the toolchain lives wherever rustup put it.
+pub enum InstalledToolchain {
+    Verified { prefix: PathBuf, target: String },
+    Rustup { target: String },
+}
+
+/// Install `version` of the Rust toolchain plus a wasm target, preferring a
+/// checksum-verified manifest-based install and falling back to
+/// `rustup install` when the manifest can't be fetched.
+///
+/// When `requested_target` is `Some`, that target is installed as declared
+/// (e.g. by a Wasm module's own build metadata) rather than auto-selected;
+/// an error is raised if the manifest says it isn't available.
+pub fn install(
+    version: &str,
+    prefix: &Path,
+    requested_target: Option<&str>,
+    cache: Option<&crate::cache::Cache>,
+) -> Result<InstalledToolchain, ToolchainError> {
+    match fetch_manifest(version) {
+        Ok(manifest) => {
+            info!("Resolved channel manifest for toolchain {}", version);
+            install_from_manifest(version, &manifest, prefix, requested_target, cache)
+        }
+        Err(err) => {
+            warn!(
+                "Could not fetch channel manifest for toolchain {} ({}), falling back to rustup install",
+                version, err
+            );
+            install_via_rustup(version, requested_target, cache)
+        }
+    }
+}
+
+fn manifest_url(version: &str) -> String {
+    format!(
+        "https://static.rust-lang.org/dist/channel-rust-{}.toml",
+        version
+    )
+}
+
+fn fetch_manifest(version: &str) -> Result<ChannelManifest, ToolchainError> {
+    let url = manifest_url(version);
+    info!("Fetching channel manifest for toolchain {} from {}", version, url);
+    let body = reqwest::blocking::get(&url)
+        .and_then(|r| r.error_for_status())
+        .and_then(|r| r.text())
+        .map_err(|source| ToolchainError::ManifestFetch {
+            version: version.to_string(),
+            source,
+        })?;
+    toml::from_str(&body).map_err(|source| ToolchainError::ManifestParse {
+        version: version.to_string(),
+        source,
+    })
+}
+
+fn resolve_wasm_target<'a>(manifest: &'a ChannelManifest, version: &str) -> Result<&'a str, ToolchainError> {
+    let rust_std = manifest
+        .packages
+        .get("rust-std")
+        .ok_or_else(|| ToolchainError::NoWasmTarget {
+            version: version.to_string(),
+            candidates: WASM_TARGET_CANDIDATES.to_vec(),
+        })?;
+    for candidate in WASM_TARGET_CANDIDATES.iter().copied() {
+        if rust_std
+            .targets
+            .get(candidate)
+            .map(|t| t.available)
+            .unwrap_or(false)
+        {
+            return Ok(candidate);
+        }
+    }
+    Err(ToolchainError::NoWasmTarget {
+        version: version.to_string(),
+        candidates: WASM_TARGET_CANDIDATES.to_vec(),
+    })
+}
+
+fn install_from_manifest(
+    version: &str,
+    manifest: &ChannelManifest,
+    prefix: &Path,
+    requested_target: Option<&str>,
+    cache: Option<&crate::cache::Cache>,
+) -> Result<InstalledToolchain, ToolchainError> {
+    let wasm_target = match requested_target {
+        Some(target) => {
+            let available = manifest
+                .packages
+                .get("rust-std")
+                .and_then(|p| p.targets.get(target))
+                .map(|t| t.available)
+                .unwrap_or(false);
+            if !available {
+                return Err(ToolchainError::ComponentUnavailable {
+                    component: "rust-std".to_string(),
+                    target: target.to_string(),
+                    version: version.to_string(),
+                });
+            }
+            info!("Using declared build target {}", target);
+            target.to_string()
+        }
+        None => {
+            let target = resolve_wasm_target(manifest, version)?.to_string();
+            info!("Using wasm target {} per channel manifest availability", target);
+            target
+        }
+    };
+
+    fs::create_dir_all(prefix)?;
+
+    install_component(manifest, version, "rustc", HOST_TARGET, prefix, cache)?;
+    install_component(manifest, version, "cargo", HOST_TARGET, prefix, cache)?;
+    install_component(manifest, version, "rust-std", HOST_TARGET, prefix, cache)?;
+    install_component(manifest, version, "rust-std", &wasm_target, prefix, cache)?;
+
+    Ok(InstalledToolchain::Verified {
+        prefix: prefix.to_path_buf(),
+        target: wasm_target,
+    })
+}
+
+fn install_component(
+    manifest: &ChannelManifest,
+    version: &str,
+    component: &str,
+    target: &str,
+    prefix: &Path,
+    cache: Option<&crate::cache::Cache>,
+) -> Result<(), ToolchainError> {
+    let unavailable = || ToolchainError::ComponentUnavailable {
+        component: component.to_string(),
+        target: target.to_string(),
+        version: version.to_string(),
+    };
+
+    let pkg = manifest
+        .packages
+        .get(component)
+        .and_then(|p| p.targets.get(target))
+        .ok_or_else(unavailable)?;
+
+    if !pkg.available {
+        return Err(unavailable());
+    }
+
+    let label = format!("{component}-{target}");
+    let (download_url, expected_hash) = match (&pkg.xz_url, &pkg.xz_hash) {
+        (Some(url), Some(hash)) => (url.clone(), hash.clone()),
+        _ => {
+            let url = pkg.url.clone().ok_or_else(unavailable)?;
+            let hash = pkg.hash.clone().ok_or_else(unavailable)?;
+            (url, hash)
+        }
+    };
+
+    info!("Downloading component {} from {}", label, download_url);
+    let fetch_component = || {
+        reqwest::blocking::get(&download_url)
+            .and_then(|r| r.bytes())
+            .map(|b| b.to_vec())
+            .map_err(|source| ToolchainError::Download {
+                component: label.clone(),
+                source,
+            })
+    };
+    // Only cache the download once its checksum has been verified, so a
+    // corrupted or tampered response never poisons the shared cache for
+    // later retries.
+    let bytes = match cache.and_then(|cache| cache.try_get(&download_url)) {
+        Some(bytes) => bytes,
+        None => fetch_component()?,
+    };
+
+    let actual_hash = hex::encode(Sha256::digest(&bytes));
+    if actual_hash != expected_hash {
+        return Err(ToolchainError::ChecksumMismatch {
+            component: label,
+            expected: expected_hash,
+            actual: actual_hash,
+        });
+    }
+    info!("Verified SHA256 checksum of component {}", label);
+
+    if let Some(cache) = cache {
+        cache.put(&download_url, &bytes)?;
+    }
+
+    let decompressed: Box<dyn std::io::Read> = if download_url.ends_with(".xz") {
+        Box::new(xz2::read::XzDecoder::new(Cursor::new(bytes)))
+    } else if download_url.ends_with(".gz") {
+        Box::new(flate2::read::GzDecoder::new(Cursor::new(bytes)))
+    } else {
+        Box::new(Cursor::new(bytes))
+    };
+    let mut archive = tar::Archive::new(decompressed);
+    archive
+        .unpack(prefix)
+        .map_err(|source| ToolchainError::Unpack {
+            component: label.clone(),
+            source,
+        })?;
+    info!("Unpacked component {} into {}", label, prefix.display());
+
+    Ok(())
+}
+
+/// `rustup install`/`rustup target add` mutate toolchain state shared by the
+/// whole machine. When batch mode verifies many entries concurrently, only
+/// one of them may run rustup at a time; everything else (downloads,
+/// building) can proceed in parallel. This is a simple advisory lock: a
+/// sentinel file created exclusively, spun on until it can be claimed.
+struct RustupLock {
+    path: PathBuf,
+}
+
+impl RustupLock {
+    fn acquire(cache: Option<&crate::cache::Cache>) -> std::io::Result<Self> {
+        let lock_dir = cache
+            .map(|c| c.root().to_path_buf())
+            .unwrap_or_else(std::env::temp_dir);
+        fs::create_dir_all(&lock_dir)?;
+        let path = lock_dir.join("rustup-install.lock");
+        loop {
+            match fs::OpenOptions::new().create_new(true).write(true).open(&path) {
+                Ok(_) => return Ok(RustupLock { path }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    std::thread::sleep(std::time::Duration::from_millis(200));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for RustupLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn install_via_rustup(
+    version: &str,
+    requested_target: Option<&str>,
+    cache: Option<&crate::cache::Cache>,
+) -> Result<InstalledToolchain, ToolchainError> {
+    let _lock = RustupLock::acquire(cache)?;
+    info!("Installing Rust toolchain {} via rustup", version);
+    let install_status = process::Command::new("rustup")
+        .args(["install", version])
+        .status()?;
+    if !install_status.success() {
+        return Err(ToolchainError::Io(std::io::Error::other(format!(
+            "rustup install {version} failed"
+        ))));
+    }
+
+    let target = match requested_target {
+        Some(target) => target.to_string(),
+        None => {
+            let version_parts: Vec<&str> = version.split('.').collect();
+            if version_parts.len() >= 2 {
+                let version_str = format!("{}.{}", version_parts[0], version_parts[1]);
+                match version_str.parse::<f32>() {
+                    Ok(v) if v > 1.84 => "wasm32v1-none".to_string(),
+                    _ => "wasm32-unknown-unknown".to_string(),
+                }
+            } else {
+                "wasm32-unknown-unknown".to_string()
+            }
+        }
+    };
+
+    info!("Adding target {} to toolchain {} via rustup", target, version);
+    let add_status = process::Command::new("rustup")
+        .args(["target", "add", &target, "--toolchain", version])
+        .status()?;
+    if !add_status.success() {
+        return Err(ToolchainError::Io(std::io::Error::other(format!(
+            "rustup target add {target} --toolchain {version} failed"
+        ))));
+    }
+
+    Ok(InstalledToolchain::Rustup {
+        target: target.to_string(),
+    })
+}
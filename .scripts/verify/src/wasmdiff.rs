@@ -0,0 +1,278 @@
+//! Structured diffing between a freshly built Wasm module and the reference
+//! module a hash mismatch was checked against, so e.g. a `producers` section
+//! mismatch (toolchain metadata) can be told apart from a code section
+//! mismatch (an actual behavioral difference).
+
+use std::collections::BTreeMap;
+use wasmparser::{Parser, Payload};
+
+#[derive(thiserror::Error, Debug)]
+pub enum DiffError {
+    #[error("failed to fetch reference Wasm module for hash {hash}: {source}")]
+    Fetch {
+        hash: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("failed to parse Wasm module: {0}")]
+    Parse(#[from] wasmparser::BinaryReaderError),
+}
+
+/// One named entity (an import or export) in declaration order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct EntityRef {
+    module: Option<String>,
+    name: String,
+    kind: String,
+}
+
+#[derive(Default)]
+struct ModuleSummary {
+    custom_sections: BTreeMap<String, Vec<u8>>,
+    imports: Vec<EntityRef>,
+    exports: Vec<EntityRef>,
+    memory_limits: Vec<(u64, Option<u64>)>,
+    code_section_len: usize,
+}
+
+fn summarize(bytes: &[u8]) -> Result<ModuleSummary, DiffError> {
+    let mut summary = ModuleSummary::default();
+    for payload in Parser::new(0).parse_all(bytes) {
+        match payload? {
+            Payload::CustomSection(reader) => {
+                summary
+                    .custom_sections
+                    .insert(reader.name().to_string(), reader.data().to_vec());
+            }
+            Payload::ImportSection(reader) => {
+                for import in reader {
+                    let import = import?;
+                    summary.imports.push(EntityRef {
+                        module: Some(import.module.to_string()),
+                        name: import.name.to_string(),
+                        kind: format!("{:?}", import.ty),
+                    });
+                }
+            }
+            Payload::ExportSection(reader) => {
+                for export in reader {
+                    let export = export?;
+                    summary.exports.push(EntityRef {
+                        module: None,
+                        name: export.name.to_string(),
+                        kind: format!("{:?}", export.kind),
+                    });
+                }
+            }
+            Payload::MemorySection(reader) => {
+                for memory in reader {
+                    let memory = memory?;
+                    summary.memory_limits.push((memory.initial, memory.maximum));
+                }
+            }
+            Payload::CodeSectionEntry(body) => {
+                summary.code_section_len += body.range().len();
+            }
+            _ => {}
+        }
+    }
+    Ok(summary)
+}
+
+/// A single line in a diff report: a section/aspect name and whether it
+/// matched between the built and reference modules.
+pub struct DiffLine {
+    pub label: String,
+    pub identical: bool,
+    pub detail: String,
+}
+
+/// Compare a freshly built Wasm module against a reference module and
+/// produce a line-by-line report of what matched and what didn't.
+pub fn diff(built: &[u8], reference: &[u8]) -> Result<Vec<DiffLine>, DiffError> {
+    let built_summary = summarize(built)?;
+    let reference_summary = summarize(reference)?;
+    let mut lines = Vec::new();
+
+    let mut section_names: Vec<&String> = built_summary
+        .custom_sections
+        .keys()
+        .chain(reference_summary.custom_sections.keys())
+        .collect();
+    section_names.sort();
+    section_names.dedup();
+    for name in section_names {
+        let built_bytes = built_summary.custom_sections.get(name);
+        let reference_bytes = reference_summary.custom_sections.get(name);
+        let (identical, detail) = match (built_bytes, reference_bytes) {
+            (Some(b), Some(r)) if b == r => (true, "byte-identical".to_string()),
+            (Some(b), Some(r)) => (
+                false,
+                format!("differs ({} bytes vs {} bytes)", b.len(), r.len()),
+            ),
+            (Some(b), None) => (false, format!("only present in built module ({} bytes)", b.len())),
+            (None, Some(r)) => (false, format!("only present in reference module ({} bytes)", r.len())),
+            (None, None) => unreachable!(),
+        };
+        lines.push(DiffLine {
+            label: format!("custom section '{name}'"),
+            identical,
+            detail,
+        });
+    }
+
+    let imports_identical = built_summary.imports == reference_summary.imports;
+    lines.push(DiffLine {
+        label: "imports".to_string(),
+        identical: imports_identical,
+        detail: if imports_identical {
+            "byte-identical set and order".to_string()
+        } else {
+            format!(
+                "{} imports in built module, {} in reference",
+                built_summary.imports.len(),
+                reference_summary.imports.len()
+            )
+        },
+    });
+
+    let exports_identical = built_summary.exports == reference_summary.exports;
+    lines.push(DiffLine {
+        label: "exports".to_string(),
+        identical: exports_identical,
+        detail: if exports_identical {
+            "byte-identical set and order".to_string()
+        } else {
+            format!(
+                "{} exports in built module, {} in reference",
+                built_summary.exports.len(),
+                reference_summary.exports.len()
+            )
+        },
+    });
+
+    let memory_identical = built_summary.memory_limits == reference_summary.memory_limits;
+    lines.push(DiffLine {
+        label: "memory limits".to_string(),
+        identical: memory_identical,
+        detail: format!(
+            "built {:?}, reference {:?}",
+            built_summary.memory_limits, reference_summary.memory_limits
+        ),
+    });
+
+    let code_identical = built_summary.code_section_len == reference_summary.code_section_len;
+    lines.push(DiffLine {
+        label: "code section".to_string(),
+        identical: code_identical,
+        detail: if code_identical {
+            format!("byte-identical length ({} bytes)", built_summary.code_section_len)
+        } else {
+            format!(
+                "length differs ({} bytes vs {} bytes)",
+                built_summary.code_section_len, reference_summary.code_section_len
+            )
+        },
+    });
+
+    Ok(lines)
+}
+
+/// URL of the reference Wasm module the stellar-contract-wasms repo
+/// publishes for a given hash.
+pub fn reference_wasm_url(hash: &str) -> String {
+    format!(
+        "https://github.com/leighmcculloch/stellar-contract-wasms/raw/refs/heads/main/wasm/{}.wasm",
+        hash
+    )
+}
+
+/// Download the reference Wasm module the stellar-contract-wasms repo
+/// publishes for a given hash.
+pub fn fetch_reference_wasm(hash: &str) -> Result<Vec<u8>, DiffError> {
+    let url = reference_wasm_url(hash);
+    let bytes = reqwest::blocking::get(&url)
+        .and_then(|r| r.bytes())
+        .map_err(|source| DiffError::Fetch {
+            hash: hash.to_string(),
+            source,
+        })?;
+    Ok(bytes.to_vec())
+}
+
+/// Render a diff report as human-readable text, one line per section/aspect.
+pub fn format_report(lines: &[DiffLine]) -> String {
+    lines
+        .iter()
+        .map(|line| {
+            if line.identical {
+                format!("  {} identical ({})", line.label, line.detail)
+            } else {
+                format!("  {} DIFFERS: {}", line.label, line.detail)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wasm_header() -> Vec<u8> {
+        let mut bytes = b"\0asm".to_vec();
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes
+    }
+
+    fn write_leb128(buf: &mut Vec<u8>, mut value: u64) {
+        loop {
+            let mut byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value != 0 {
+                byte |= 0x80;
+            }
+            buf.push(byte);
+            if value == 0 {
+                break;
+            }
+        }
+    }
+
+    fn custom_section(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut payload = Vec::new();
+        write_leb128(&mut payload, name.len() as u64);
+        payload.extend_from_slice(name.as_bytes());
+        payload.extend_from_slice(data);
+        let mut section = vec![0u8];
+        write_leb128(&mut section, payload.len() as u64);
+        section.extend_from_slice(&payload);
+        section
+    }
+
+    #[test]
+    fn empty_modules_are_identical() {
+        let module = wasm_header();
+        let lines = diff(&module, &module).unwrap();
+        assert!(lines.iter().all(|l| l.identical));
+    }
+
+    #[test]
+    fn matching_custom_sections_are_identical() {
+        let mut module = wasm_header();
+        module.extend(custom_section("producers", b"rustc"));
+        let lines = diff(&module, &module).unwrap();
+        assert!(lines.iter().all(|l| l.identical));
+    }
+
+    #[test]
+    fn differing_custom_sections_are_flagged() {
+        let mut built = wasm_header();
+        built.extend(custom_section("producers", b"rustc-1"));
+        let mut reference = wasm_header();
+        reference.extend(custom_section("producers", b"rustc-2"));
+        let lines = diff(&built, &reference).unwrap();
+        let producers_line = lines.iter().find(|l| l.label.contains("producers")).unwrap();
+        assert!(!producers_line.identical);
+    }
+}